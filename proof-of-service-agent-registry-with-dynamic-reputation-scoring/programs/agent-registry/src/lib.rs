@@ -1,4 +1,7 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions;
+use anchor_lang::system_program;
 
 declare_id!("AgentRegistr111111111111111111111111111111111");
 
@@ -10,65 +13,653 @@ pub mod agent_registry {
         let agent = &mut ctx.accounts.agent;
         agent.authority = ctx.accounts.authority.key();
         agent.metadata = metadata;
+        agent.half_life_slots = DEFAULT_HALF_LIFE_SLOTS;
+        agent.decay_factor_q32 = recompute_decay_factor(DEFAULT_HALF_LIFE_SLOTS);
+        agent.last_update_slot = Clock::get()?.slot;
         Ok(())
     }
 
-    pub fn record_reputation(ctx: Context<RecordReputation>, delta: ReputationDelta) -> Result<()> {
+    /// Registers a stake-weighted attester for an agent. `stake` must be
+    /// backed by an actual lamport transfer into the attester's PDA vault —
+    /// it's never taken as a bare instruction argument the caller can make
+    /// up, since an unbacked number would give sybil inflation no
+    /// resistance at all. Stake is added to the agent's `total_stake`
+    /// denominator, so later attestations from this attester move the score
+    /// in proportion to their locked share of it.
+    pub fn register_attester(ctx: Context<RegisterAttester>, stake: u64) -> Result<()> {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            stake,
+        )?;
+
+        let attester = &mut ctx.accounts.attester;
+        attester.agent = ctx.accounts.agent.key();
+        attester.authority = ctx.accounts.authority.key();
+        attester.stake = stake;
+
+        let agent = &mut ctx.accounts.agent;
+        agent.total_stake = agent.total_stake.saturating_add(stake);
+        Ok(())
+    }
+
+    /// Applies a client-attested, stake-weighted delta to an agent's score
+    /// and appends the same attested delta to its `ReputationLog`, so the
+    /// log stays fed by the one attested path rather than a separate
+    /// authority-trusted instruction.
+    /// The authority key is never trusted for this: the instructions sysvar
+    /// must show the preceding instruction as an Ed25519 program
+    /// verification whose pubkey/message match the reconstructed
+    /// attestation payload exactly, and the signer must be the named
+    /// attester.
+    pub fn submit_attestation(ctx: Context<SubmitAttestation>, payload: AttestationPayload) -> Result<()> {
+        let agent_key = ctx.accounts.reputation_update.agent.key();
+        let expected_message = attestation_message(&agent_key, &payload);
+        let attester_pubkey = verify_attestation(&ctx.accounts.instructions, &expected_message)?;
+        require_keys_eq!(
+            attester_pubkey,
+            ctx.accounts.reputation_update.attester.authority,
+            AgentRegistryError::AttestationMismatch
+        );
+
+        let weighted = ctx
+            .accounts
+            .reputation_update
+            .apply(payload.score_change, payload.reference)?;
+
+        let log = &mut ctx.accounts.log;
+        let serial = log
+            .serial
+            .checked_add(1)
+            .ok_or(AgentRegistryError::SerialOverflow)?;
+        log.serial = serial;
+        if log.entries.len() >= REPUTATION_LOG_CAPACITY {
+            log.entries.remove(0);
+        }
+        log.entries.push(LogEntry {
+            serial,
+            delta: ReputationDelta {
+                score_change: weighted,
+                reference: payload.reference,
+            },
+            withdrawn: false,
+            attester: attester_pubkey,
+        });
+        Ok(())
+    }
+
+    /// Sets the half-life, in slots, used to decay this agent's score, and
+    /// recomputes the cached per-slot decay factor to match. The factor is
+    /// an expensive binary search (see `decay_factor_per_slot`), so it's
+    /// computed once here rather than on every `submit_attestation`.
+    pub fn set_half_life(ctx: Context<SetHalfLife>, half_life_slots: u64) -> Result<()> {
+        let agent = &mut ctx.accounts.agent;
+        agent.half_life_slots = half_life_slots;
+        agent.decay_factor_q32 = recompute_decay_factor(half_life_slots);
+        Ok(())
+    }
+
+    /// Read-only view of the current decayed score: applies the same decay
+    /// as `submit_attestation` without writing, so off-chain readers see the
+    /// live value between updates.
+    pub fn effective_score(ctx: Context<ViewAgent>) -> Result<i64> {
+        let agent = &ctx.accounts.agent;
+        let elapsed = Clock::get()?.slot.saturating_sub(agent.last_update_slot);
+        Ok(decay_score(
+            agent.reputation_score,
+            agent.decay_factor_q32,
+            agent.half_life_slots,
+            elapsed,
+        ))
+    }
+
+    /// Creates the append-only reputation log for an agent, fixing the session id
+    /// that consumers must match before trusting incremental replay.
+    pub fn init_reputation_log(ctx: Context<InitReputationLog>, session: [u8; 16]) -> Result<()> {
+        let log = &mut ctx.accounts.log;
+        log.agent = ctx.accounts.agent.key();
+        log.session = session;
+        log.serial = 0;
+        log.snapshot_serial = 0;
+        log.entries = Vec::new();
+        Ok(())
+    }
+
+    /// Reverses the score change applied by `serial`, guarding against
+    /// double-withdrawal by checking the entry's own `withdrawn` flag.
+    /// Like `submit_attestation`, this never trusts the agent's authority:
+    /// the caller must produce a fresh Ed25519-signed `WithdrawalPayload`
+    /// from the *same attester* who originally published that serial,
+    /// otherwise an unattested reversal could re-inflate a score by
+    /// cancelling a negative delta (or erase a real positive one).
+    pub fn withdraw_delta(ctx: Context<WithdrawDelta>, payload: WithdrawalPayload) -> Result<()> {
+        let agent_key = ctx.accounts.agent.key();
+        let expected_message = withdrawal_message(&agent_key, &payload);
+        let attester_pubkey = verify_attestation(&ctx.accounts.instructions, &expected_message)?;
+        require_keys_eq!(
+            attester_pubkey,
+            ctx.accounts.attester.authority,
+            AgentRegistryError::AttestationMismatch
+        );
+
+        let log = &mut ctx.accounts.log;
+        let entry = log
+            .entries
+            .iter_mut()
+            .find(|entry| entry.serial == payload.serial)
+            .ok_or(AgentRegistryError::UnknownSerial)?;
+        require!(!entry.withdrawn, AgentRegistryError::AlreadyWithdrawn);
+        require_keys_eq!(
+            entry.attester,
+            attester_pubkey,
+            AgentRegistryError::AttestationMismatch
+        );
+
+        let reversed = entry.delta.score_change;
+        entry.withdrawn = true;
+
         let agent = &mut ctx.accounts.agent;
-        agent.reputation_score = (agent.reputation_score + delta.score_change)
-            .clamp(0, 10_000);
-        agent.last_event = delta;
+        let current_slot = Clock::get()?.slot;
+        let elapsed = current_slot.saturating_sub(agent.last_update_slot);
+        let decayed = decay_score(
+            agent.reputation_score,
+            agent.decay_factor_q32,
+            agent.half_life_slots,
+            elapsed,
+        );
+        agent.reputation_score = (decayed - reversed).clamp(0, 10_000);
+        agent.last_update_slot = current_slot;
+        Ok(())
+    }
+
+    /// Collapses the log up to the current serial into the running score and
+    /// prunes entries that are now covered by the snapshot. This doesn't
+    /// move the score, only checkpoints it, so it stays authority-gated.
+    pub fn snapshot(ctx: Context<SnapshotLog>) -> Result<()> {
+        let log = &mut ctx.accounts.log;
+        let snapshot_serial = log.serial;
+        log.snapshot_serial = snapshot_serial;
+        log.entries.retain(|entry| entry.serial > snapshot_serial);
         Ok(())
     }
 }
 
 #[derive(Accounts)]
 pub struct RegisterAgent<'info> {
-    #[account(init, payer = authority, space = AgentState::LEN)]
+    #[account(init, payer = authority, space = 8 + AgentState::INIT_SPACE)]
+    pub agent: Account<'info, AgentState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Composable core of a stake-weighted reputation update: the agent being
+/// rated, the attester vouching for it, and the nullifier that prevents the
+/// attestation from being replayed. Downstream programs (e.g. a marketplace
+/// settling a job) can nest this struct inside their own `#[derive(Accounts)]`
+/// context and call [`ReputationUpdate::apply`] to update reputation as part
+/// of a larger composed instruction, with no CPI required.
+#[derive(Accounts)]
+#[instruction(payload: AttestationPayload)]
+pub struct ReputationUpdate<'info> {
+    #[account(mut)]
+    pub agent: Account<'info, AgentState>,
+    #[account(has_one = agent)]
+    pub attester: Account<'info, Attester>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConsumedAttestation::INIT_SPACE,
+        seeds = [b"attestation", agent.key().as_ref(), payload.reference.as_ref()],
+        bump
+    )]
+    pub consumed: Account<'info, ConsumedAttestation>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReputationUpdate<'info> {
+    /// Folds a stake-weighted, decayed delta into the agent and records the
+    /// reference as consumed. Shared by the standalone `submit_attestation`
+    /// instruction and any composite context that embeds this struct.
+    pub fn apply(&mut self, score_change: i64, reference: [u8; 32]) -> Result<i64> {
+        self.consumed.reference = reference;
+
+        let weighted = weighted_score_change(score_change, self.attester.stake, self.agent.total_stake);
+
+        let current_slot = Clock::get()?.slot;
+        let elapsed = current_slot.saturating_sub(self.agent.last_update_slot);
+        let decayed = decay_score(
+            self.agent.reputation_score,
+            self.agent.decay_factor_q32,
+            self.agent.half_life_slots,
+            elapsed,
+        );
+
+        self.agent.reputation_score = (decayed + weighted).clamp(0, 10_000);
+        self.agent.last_event = ReputationDelta {
+            score_change: weighted,
+            reference,
+        };
+        self.agent.last_update_slot = current_slot;
+        Ok(weighted)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(payload: AttestationPayload)]
+pub struct SubmitAttestation<'info> {
+    pub reputation_update: ReputationUpdate<'info>,
+    #[account(
+        mut,
+        seeds = [b"reputation-log", reputation_update.agent.key().as_ref()],
+        bump
+    )]
+    pub log: Account<'info, ReputationLog>,
+    /// CHECK: verified in the handler via Ed25519 sysvar instruction introspection.
+    #[account(address = instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAttester<'info> {
+    #[account(mut)]
     pub agent: Account<'info, AgentState>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Attester::INIT_SPACE,
+        seeds = [b"attester", agent.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub attester: Account<'info, Attester>,
+    /// Escrow PDA that actually holds the attester's staked lamports. Stake
+    /// only counts toward `total_stake` once it's locked up here, not on the
+    /// strength of the caller's say-so.
+    #[account(
+        mut,
+        seeds = [b"stake-vault", agent.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RecordReputation<'info> {
+pub struct SetHalfLife<'info> {
     #[account(mut, has_one = authority)]
     pub agent: Account<'info, AgentState>,
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ViewAgent<'info> {
+    pub agent: Account<'info, AgentState>,
+}
+
+#[derive(Accounts)]
+pub struct InitReputationLog<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ReputationLog::INIT_SPACE,
+        seeds = [b"reputation-log", agent.key().as_ref()],
+        bump
+    )]
+    pub log: Account<'info, ReputationLog>,
+    #[account(has_one = authority)]
+    pub agent: Account<'info, AgentState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotLog<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation-log", agent.key().as_ref()],
+        bump
+    )]
+    pub log: Account<'info, ReputationLog>,
+    #[account(has_one = authority)]
+    pub agent: Account<'info, AgentState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(payload: WithdrawalPayload)]
+pub struct WithdrawDelta<'info> {
+    #[account(
+        mut,
+        seeds = [b"reputation-log", agent.key().as_ref()],
+        bump
+    )]
+    pub log: Account<'info, ReputationLog>,
+    #[account(mut)]
+    pub agent: Account<'info, AgentState>,
+    #[account(has_one = agent)]
+    pub attester: Account<'info, Attester>,
+    /// CHECK: verified in the handler via Ed25519 sysvar instruction introspection.
+    #[account(address = instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
 #[account]
-#[derive(Default)]
+#[derive(InitSpace, Default)]
 pub struct AgentState {
     pub authority: Pubkey,
     pub metadata: AgentMetadata,
     pub reputation_score: i64,
     pub last_event: ReputationDelta,
+    pub last_update_slot: u64,
+    pub half_life_slots: u64,
+    /// Cached output of `decay_factor_per_slot(half_life_slots)`, the Q32
+    /// per-slot decay factor. Recomputed only in `register_agent` and
+    /// `set_half_life`, so hot paths never repeat the binary search.
+    pub decay_factor_q32: u128,
+    pub total_stake: u64,
 }
 
-impl AgentState {
-    pub const LEN: usize = 8 + 32 + AgentMetadata::LEN + 8 + ReputationDelta::LEN;
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Default)]
 pub struct AgentMetadata {
-    pub capabilities_uri: [u8; 64],
+    #[max_len(200)]
+    pub capabilities_uri: String,
     pub disclosure: u8,
 }
 
-impl AgentMetadata {
-    pub const LEN: usize = 64 + 1;
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Default)]
+pub struct ReputationDelta {
+    pub score_change: i64,
+    pub reference: [u8; 32],
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
-pub struct ReputationDelta {
+/// Maximum number of recent deltas kept in a `ReputationLog` before the
+/// oldest entry is dropped. Consumers that need older history must have
+/// replayed it before it ages out, or resync from a `snapshot`.
+pub const REPUTATION_LOG_CAPACITY: usize = 32;
+
+/// Append-only audit trail for an agent's reputation, modeled on RRDP-style
+/// publish/withdraw deltas. `serial` only ever increases; `session` is fixed
+/// at init and must be checked by consumers before applying incremental
+/// deltas — a changed session means the log was reset and a full
+/// `snapshot` must be re-fetched instead.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ReputationLog {
+    pub agent: Pubkey,
+    pub session: [u8; 16],
+    pub serial: u64,
+    pub snapshot_serial: u64,
+    #[max_len(REPUTATION_LOG_CAPACITY)]
+    pub entries: Vec<LogEntry>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Default)]
+pub struct LogEntry {
+    pub serial: u64,
+    pub delta: ReputationDelta,
+    pub withdrawn: bool,
+    /// The attester whose Ed25519-signed payload produced this entry.
+    /// `withdraw_delta` requires a fresh signature from this same pubkey.
+    pub attester: Pubkey,
+}
+
+/// Default half-life for reputation decay, in slots (~1 day at 400ms/slot).
+pub const DEFAULT_HALF_LIFE_SLOTS: u64 = 216_000;
+
+/// Q32 fixed-point scale: `Q32_ONE` represents `1.0`.
+const Q32_ONE: u128 = 1 << 32;
+
+/// Number of half-lives after which a score has decayed below any
+/// representable value, used to short-circuit the exponentiation.
+const HALF_LIVES_TO_ZERO: u64 = 64;
+
+/// Raises a Q32 fixed-point `base` to an integer power via
+/// exponentiation-by-squaring over the binary digits of `exp`.
+fn pow_q32(mut base: u128, mut exp: u64) -> u128 {
+    let mut result = Q32_ONE;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = (result * base) >> 32;
+        }
+        base = (base * base) >> 32;
+        exp >>= 1;
+        if base == 0 && result == 0 {
+            break;
+        }
+    }
+    result
+}
+
+/// Finds the Q32 fixed-point `half_life_slots`-th root of `0.5`, i.e. the
+/// per-slot decay factor such that `factor ^ half_life_slots == 0.5`, via
+/// binary search (each probe reuses `pow_q32`). This is ~64 * 64 multiply/
+/// shift steps, too expensive to redo on every `submit_attestation` or
+/// `effective_score` call — callers should go through `recompute_decay_factor`
+/// and cache the result on `AgentState::decay_factor_q32` instead of calling
+/// this directly from a hot path.
+fn decay_factor_per_slot(half_life_slots: u64) -> u128 {
+    let target = Q32_ONE / 2;
+    let mut lo: u128 = 0;
+    let mut hi: u128 = Q32_ONE;
+    for _ in 0..64 {
+        let mid = lo + (hi - lo) / 2;
+        if pow_q32(mid, half_life_slots) > target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    lo + (hi - lo) / 2
+}
+
+/// Recomputes the cached per-slot decay factor for a given half-life. Called
+/// only from `register_agent` and `set_half_life`, i.e. whenever
+/// `half_life_slots` actually changes. A half-life of `0` (decay disabled)
+/// has no meaningful factor, so it's left as `0` and never read since
+/// `decay_score` short-circuits on `half_life_slots == 0` first.
+fn recompute_decay_factor(half_life_slots: u64) -> u128 {
+    if half_life_slots == 0 {
+        return 0;
+    }
+    decay_factor_per_slot(half_life_slots)
+}
+
+/// Decays `score` by `elapsed` slots under the given half-life, computing
+/// `score * 2^(-elapsed / half_life_slots)` in fixed point, then clamps to
+/// `[0, 10_000]`. A `half_life_slots` of `0` disables decay. `decay_factor_q32`
+/// must be `AgentState::decay_factor_q32`, i.e. the cached output of
+/// `recompute_decay_factor(half_life_slots)` — this function only ever raises
+/// it to the `elapsed`-th power, it never re-derives it.
+fn decay_score(score: i64, decay_factor_q32: u128, half_life_slots: u64, elapsed: u64) -> i64 {
+    if half_life_slots == 0 || elapsed == 0 {
+        return score.clamp(0, 10_000);
+    }
+    if elapsed >= half_life_slots.saturating_mul(HALF_LIVES_TO_ZERO) {
+        return 0;
+    }
+
+    let decayed = pow_q32(decay_factor_q32, elapsed);
+    let scaled = (score as i128 * decayed as i128) >> 32;
+    (scaled as i64).clamp(0, 10_000)
+}
+
+/// The signed payload a client attests to off-chain: it binds the agent
+/// being rated, a service-session reference, a nonce, and the score change
+/// the client is vouching for.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Default)]
+pub struct AttestationPayload {
+    pub reference: [u8; 32],
+    pub nonce: u64,
     pub score_change: i64,
+}
+
+/// Records one attester's stake against an agent. `stake` is the numerator
+/// and `AgentState::total_stake` the denominator in the weighted-delta
+/// formula, mirroring the commitment/total-stake shape used for Solana
+/// block-commitment weighting.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Attester {
+    pub agent: Pubkey,
+    pub authority: Pubkey,
+    pub stake: u64,
+}
+
+/// Scales `score_change` by `attester_stake / total_stake`, clamped to the
+/// representable `i64` range. An attester with no stake, or an agent with
+/// no stake registered at all, contributes nothing.
+fn weighted_score_change(score_change: i64, attester_stake: u64, total_stake: u64) -> i64 {
+    if total_stake == 0 {
+        return 0;
+    }
+    let weighted = (score_change as i128 * attester_stake as i128) / total_stake as i128;
+    weighted.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Marks a `reference` as spent so an attestation can never be replayed.
+/// Existence of the PDA *is* the guard: `submit_attestation` creates it with
+/// `init`, which fails if the reference was already consumed.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ConsumedAttestation {
     pub reference: [u8; 32],
 }
 
-impl ReputationDelta {
-    pub const LEN: usize = 8 + 32;
+/// Builds the exact byte layout a client must sign: agent pubkey, then the
+/// attestation payload fields in declaration order.
+fn attestation_message(agent: &Pubkey, payload: &AttestationPayload) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 32 + 8 + 8);
+    message.extend_from_slice(agent.as_ref());
+    message.extend_from_slice(&payload.reference);
+    message.extend_from_slice(&payload.nonce.to_le_bytes());
+    message.extend_from_slice(&payload.score_change.to_le_bytes());
+    message
+}
+
+/// The signed payload an attester uses to reverse a `ReputationLog` entry it
+/// previously published, identified by `serial`.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Default)]
+pub struct WithdrawalPayload {
+    pub serial: u64,
+    pub nonce: u64,
+}
+
+/// Builds the exact byte layout a client must sign to withdraw a serial.
+/// Tagged with a domain separator so a withdrawal signature can never be
+/// replayed as, or confused with, an `AttestationPayload` signature.
+fn withdrawal_message(agent: &Pubkey, payload: &WithdrawalPayload) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 8 + 8 + 8);
+    message.extend_from_slice(agent.as_ref());
+    message.extend_from_slice(b"withdraw");
+    message.extend_from_slice(&payload.serial.to_le_bytes());
+    message.extend_from_slice(&payload.nonce.to_le_bytes());
+    message
+}
+
+/// Sentinel value `Ed25519SignatureOffsets` instruction-index fields carry
+/// when they point at "this instruction" rather than another one in the
+/// transaction.
+const ED25519_CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Extracts the signer pubkey and signed message from a single-signature
+/// Ed25519 program instruction, per the offsets layout documented for
+/// `Ed25519SignatureOffsets`. Requires the signature/pubkey/message index
+/// fields all name "this instruction" — otherwise the native program
+/// verifies the signature against *different* instruction data than the
+/// plaintext pubkey/message this function would read, letting an attacker
+/// pair a validly-signed throwaway message with a forged pubkey/message
+/// planted in this instruction's own data.
+fn parse_ed25519_instruction(data: &[u8]) -> Result<(Pubkey, Vec<u8>)> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+
+    require!(
+        data.len() >= OFFSETS_START + OFFSETS_LEN,
+        AgentRegistryError::MalformedEd25519Instruction
+    );
+    require!(data[0] == 1, AgentRegistryError::MalformedEd25519Instruction);
+
+    let offsets = &data[OFFSETS_START..OFFSETS_START + OFFSETS_LEN];
+    let signature_instruction_index = u16::from_le_bytes([offsets[2], offsets[3]]);
+    let public_key_offset = u16::from_le_bytes([offsets[4], offsets[5]]) as usize;
+    let public_key_instruction_index = u16::from_le_bytes([offsets[6], offsets[7]]);
+    let message_data_offset = u16::from_le_bytes([offsets[8], offsets[9]]) as usize;
+    let message_data_size = u16::from_le_bytes([offsets[10], offsets[11]]) as usize;
+    let message_instruction_index = u16::from_le_bytes([offsets[12], offsets[13]]);
+
+    require!(
+        signature_instruction_index == ED25519_CURRENT_INSTRUCTION
+            && public_key_instruction_index == ED25519_CURRENT_INSTRUCTION
+            && message_instruction_index == ED25519_CURRENT_INSTRUCTION,
+        AgentRegistryError::MalformedEd25519Instruction
+    );
+
+    require!(
+        data.len() >= public_key_offset + 32,
+        AgentRegistryError::MalformedEd25519Instruction
+    );
+    let mut pubkey_bytes = [0u8; 32];
+    pubkey_bytes.copy_from_slice(&data[public_key_offset..public_key_offset + 32]);
+
+    require!(
+        data.len() >= message_data_offset + message_data_size,
+        AgentRegistryError::MalformedEd25519Instruction
+    );
+    let message = data[message_data_offset..message_data_offset + message_data_size].to_vec();
+
+    Ok((Pubkey::new_from_array(pubkey_bytes), message))
+}
+
+/// Locates the Ed25519 program verification expected immediately before the
+/// current instruction, checks its signed message against `expected_message`,
+/// and returns the pubkey that signed it. Shared by every instruction that
+/// needs a client attestation, so the sysvar-introspection logic lives in
+/// exactly one place.
+fn verify_attestation(
+    instructions_sysvar: &UncheckedAccount,
+    expected_message: &[u8],
+) -> Result<Pubkey> {
+    let current_index = instructions::load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, AgentRegistryError::MissingEd25519Instruction);
+    let ed25519_ix =
+        instructions::load_instruction_at_checked((current_index - 1) as usize, instructions_sysvar)?;
+    require_keys_eq!(
+        ed25519_ix.program_id,
+        ed25519_program::ID,
+        AgentRegistryError::MissingEd25519Instruction
+    );
+
+    let (attester_pubkey, signed_message) = parse_ed25519_instruction(&ed25519_ix.data)?;
+    require!(
+        signed_message == expected_message,
+        AgentRegistryError::AttestationMismatch
+    );
+    Ok(attester_pubkey)
 }
 
+#[error_code]
+pub enum AgentRegistryError {
+    #[msg("reputation log serial would overflow u64")]
+    SerialOverflow,
+    #[msg("no log entry exists for the given serial")]
+    UnknownSerial,
+    #[msg("serial has already been withdrawn")]
+    AlreadyWithdrawn,
+    #[msg("expected the preceding instruction to be an Ed25519 program verification")]
+    MissingEd25519Instruction,
+    #[msg("Ed25519 instruction data is not in the expected layout")]
+    MalformedEd25519Instruction,
+    #[msg("attestation message does not match the signed Ed25519 message")]
+    AttestationMismatch,
+}
 